@@ -5,15 +5,33 @@ extern crate rand;
 
 use std::any::Any;
 use std::char;
+use std::collections::{HashMap, BTreeMap, HashSet, BTreeSet, VecDeque, LinkedList};
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::hash::Hash;
+use std::io::{Read, Write};
+use std::mem;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::num::ToPrimitive;
 use std::cmp::min;
 use std::fmt::Debug;
-use rand::random;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use rand::Rng;
 use rand::StdRng;
 use rand::SeedableRng;
 use rand::distributions::range::Range;
 use rand::distributions::IndependentSample;
 
+// One in `PROBLEM_VALUE_CHANCE` calls to a numeric `grow` returns a
+// known-troublesome value (0, MIN, MAX, NaN, ...) instead of a uniformly
+// sampled one. Numeric bugs cluster at
+// the edges of the range, so it's worth biasing toward them even though they
+// make up a vanishingly small fraction of the value space.
+const PROBLEM_VALUE_CHANCE: u32 = 10;
+
 pub type Seed = Vec<usize>; // seed for StdRng
 
 #[derive(Debug)]
@@ -22,6 +40,8 @@ pub struct Config {
     max_size: f64,
     max_tests: i64,
     max_shrinks: i64,
+    name: String, // identifies this property in the failure database
+    replay_dir: Option<PathBuf>, // None disables the failure database
 }
 
 #[derive(Debug)]
@@ -45,9 +65,41 @@ impl<Input: Debug> Run<Input> {
     }
 }
 
-pub trait Arbitrary {
+// `shrink` returns a deterministic, exhaustive-ish sequence of candidates
+// strictly smaller than `self`, instead of one random candidate. `run` walks
+// this sequence greedily (see below), so the reported `shrunk_input` is a
+// local minimum rather than whatever random walk happened to land on.
+//
+// Shrinking works on values, not on a record of the random draws that
+// produced them: the candidate generators below (integers, `Vec`, `String`,
+// tuples, ...) each decide what "smaller" means for their own type, and a
+// nested structure shrinks only through its components' `shrink`.
+pub trait Arbitrary: Sized {
     fn grow(rng: &mut StdRng, size: f64) -> Self;
-    fn shrink(rng: &mut StdRng, &Self) -> Self;
+    fn shrink(&self) -> Box<Iterator<Item=Self>>;
+}
+
+/// Serialization hook used to persist a failing input to the failure
+/// database and reconstruct it on a later run, so a bug found once stays a
+/// regression test whether or not the same seed turns it up again.
+/// `decode` reads from the front of `bytes` and advances it past whatever it
+/// consumed, so composite impls can decode their fields back to back.
+pub trait Replayable: Sized {
+    fn encode(&self, out: &mut Vec<u8>);
+    fn decode(bytes: &mut &[u8]) -> Self;
+}
+
+// Reads a one-byte sum-type tag, defaulting to 0 on an exhausted buffer
+// rather than panicking, so decoding a tag stays as total as the int impls'
+// documented zero-padding on a truncated buffer.
+fn decode_tag(bytes: &mut &[u8]) -> u8 {
+    if bytes.is_empty() {
+        0
+    } else {
+        let tag = bytes[0];
+        *bytes = &bytes[1..];
+        tag
+    }
 }
 
 fn print_panic(panic: Box<Any + Send>) -> String {
@@ -59,7 +111,101 @@ fn catch<Input: Send + 'static>(function: fn(Input) -> (), input: Input) -> Resu
     handle.join().map_err(print_panic)
 }
 
-pub fn run<Input: Arbitrary + Clone + Send + 'static>(f: fn(Input) -> (), config: &Config) -> Run<Input> {
+// Greedy fixpoint: take the first candidate that still fails, restart
+// shrinking from it, and stop once nothing in a full pass over the
+// candidates fails (or the budget runs out). Shared between freshly
+// generated failures and ones replayed from the failure database.
+fn shrink_to_fixpoint<Input: Arbitrary + Clone + Send + 'static>(
+    f: fn(Input) -> (), mut shrunk_input: Input, mut shrunk_failure: String, max_shrinks: i64
+) -> (Input, String) {
+    let mut shrinks = 0;
+    loop {
+        let mut found_smaller = false;
+        for candidate in shrunk_input.shrink() {
+            shrinks += 1;
+            let result = catch(f, candidate.clone());
+            if result.is_err() {
+                shrunk_input = candidate;
+                shrunk_failure = result.unwrap_err();
+                found_smaller = true;
+                break;
+            }
+            if shrinks >= max_shrinks {
+                break;
+            }
+        }
+        if !found_smaller || shrinks >= max_shrinks {
+            break;
+        }
+    }
+    (shrunk_input, shrunk_failure)
+}
+
+fn failure_db_path(dir: &Path, name: &str) -> PathBuf {
+    dir.join(name)
+}
+
+// The database is a flat file of length-prefixed (a `u64` length, then that
+// many bytes) `Replayable`-encoded inputs, one per past failure.
+// Missing or unreadable files are treated as "no failures yet" rather than
+// an error, since there's nothing to replay on the very first run.
+fn load_failures<Input: Replayable>(path: &Path) -> Vec<Input> {
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(_) => return Vec::new(),
+    };
+    let mut contents = Vec::new();
+    if file.read_to_end(&mut contents).is_err() {
+        return Vec::new();
+    }
+    let mut cursor: &[u8] = &contents;
+    let mut inputs = Vec::new();
+    while cursor.len() >= 8 {
+        let len = u64::decode(&mut cursor) as usize;
+        if cursor.len() < len {
+            break;
+        }
+        let mut entry = &cursor[..len];
+        inputs.push(Replayable::decode(&mut entry));
+        cursor = &cursor[len..];
+    }
+    inputs
+}
+
+fn append_failure<Input: Replayable>(path: &Path, input: &Input) {
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    let mut bytes = Vec::new();
+    input.encode(&mut bytes);
+    let mut framed = Vec::new();
+    (bytes.len() as u64).encode(&mut framed);
+    framed.extend(bytes);
+    if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path) {
+        let _ = file.write_all(&framed);
+    }
+}
+
+pub fn run<Input: Arbitrary + Replayable + Clone + Send + 'static>(f: fn(Input) -> (), config: &Config) -> Run<Input> {
+    if let Some(ref dir) = config.replay_dir {
+        let path = failure_db_path(dir, &config.name);
+        for replayed_input in load_failures::<Input>(&path) {
+            let result = catch(f, replayed_input.clone());
+            if result.is_err() {
+                let failure = result.unwrap_err();
+                let (shrunk_input, shrunk_failure) =
+                    shrink_to_fixpoint(f, replayed_input.clone(), failure.clone(), config.max_shrinks);
+                return Run::Failure {
+                    num_tests: 0,
+                    input: replayed_input,
+                    failure: failure,
+                    shrunk_input: shrunk_input,
+                    shrunk_failure: shrunk_failure,
+                }
+            }
+        }
+    }
+
     let mut rng: StdRng = SeedableRng::from_seed(config.seed.as_slice());
     for test in (0..config.max_tests) {
         let size = config.max_size * (test.to_f64().unwrap() / config.max_tests.to_f64().unwrap());
@@ -67,15 +213,11 @@ pub fn run<Input: Arbitrary + Clone + Send + 'static>(f: fn(Input) -> (), config
         let result = catch(f, input.clone());
         if result.is_err() {
             let failure = result.unwrap_err();
-            let mut shrunk_input = input.clone();
-            let mut shrunk_failure = failure.clone();
-            for _ in (0..config.max_shrinks) {
-                let next_shrunk_input = Arbitrary::shrink(&mut rng, &shrunk_input);
-                let result = catch(f, next_shrunk_input.clone());
-                if result.is_err() {
-                    shrunk_input = next_shrunk_input;
-                    shrunk_failure = result.unwrap_err();
-                }
+            let (shrunk_input, shrunk_failure) =
+                shrink_to_fixpoint(f, input.clone(), failure.clone(), config.max_shrinks);
+            if let Some(ref dir) = config.replay_dir {
+                let path = failure_db_path(dir, &config.name);
+                append_failure(&path, &shrunk_input);
             }
             return Run::Failure {
                 num_tests: test,
@@ -89,28 +231,286 @@ pub fn run<Input: Arbitrary + Clone + Send + 'static>(f: fn(Input) -> (), config
     return Run::Success
 }
 
-pub fn check<Input: Arbitrary + Debug + Clone + Send + 'static>(f: fn(Input) -> (), config: &Config) {
+pub fn check<Input: Arbitrary + Replayable + Debug + Clone + Send + 'static>(f: fn(Input) -> (), config: &Config) {
     run(f, config).unwrap();
 }
 
-impl Arbitrary for u32 {
-    fn grow(rng: &mut StdRng, size: f64) -> u32 {
-        Range::new(0, size.to_u32().unwrap() + 1).ind_sample(rng)
+/// Picks uniformly among `generators` and runs the chosen one. For enums
+/// there's no way to derive `Arbitrary` automatically, so this (and
+/// `frequency`) is the intended way to hand-write one: match each variant to
+/// a generator function and let `one_of`/`frequency` do the picking.
+pub fn one_of<T>(rng: &mut StdRng, size: f64, generators: &[fn(&mut StdRng, f64) -> T]) -> T {
+    assert!(!generators.is_empty(), "one_of: generators must be non-empty");
+    let ix = Range::new(0, generators.len()).ind_sample(rng);
+    generators[ix](rng, size)
+}
+
+/// Like `one_of`, but picks a generator with probability proportional to its
+/// weight: builds a cumulative-weight table and scans it for the leftmost
+/// bucket past a single sample in `[0, total_weight)`.
+///
+/// For recursive data (trees, nested lists, ...) pass a weight of `0` for
+/// recursive branches once `size` has been divided down to ~0, and invoke
+/// recursive sub-generators with `size / k` for some `k > 1` rather than
+/// `size` unchanged — otherwise the recursion never bottoms out.
+pub fn frequency<T>(rng: &mut StdRng, size: f64, choices: &[(u32, fn(&mut StdRng, f64) -> T)]) -> T {
+    let mut cumulative_weights = Vec::with_capacity(choices.len());
+    let mut total_weight = 0u32;
+    for &(weight, _) in choices.iter() {
+        total_weight += weight;
+        cumulative_weights.push(total_weight);
+    }
+    assert!(total_weight > 0, "frequency: choices must be non-empty and have a non-zero total weight");
+    let point = Range::new(0, total_weight).ind_sample(rng);
+    // Leftmost bucket whose cumulative weight exceeds `point`. `binary_search`
+    // doesn't work here: a zero-weight bucket shares its cumulative value
+    // with its predecessor, and which of the equal entries it returns is
+    // unspecified, so it could resolve to the zero-weight bucket itself.
+    // Scanning for the first `point < cw` always lands on the earlier,
+    // non-zero-weight entry instead.
+    let ix = cumulative_weights.iter().position(|&cw| point < cw).unwrap();
+    choices[ix].1(rng, size)
+}
+
+// Builds the "toward zero by binary search" candidate sequence shared by all
+// integer impls: 0, then successively closer approximations of `value`
+// (value/2, 3*value/4, ...), stopping just short of `value` itself. Eager
+// rather than a custom `Iterator` impl, since the sequence is always short
+// (O(log value)) and this keeps every numeric impl a one-liner.
+macro_rules! int_shrink_candidates {
+    ($value:expr) => {{
+        let value = $value;
+        let mut candidates = Vec::new();
+        if value != 0 {
+            candidates.push(0);
+        }
+        let mut current = 0;
+        loop {
+            let diff = value - current;
+            if diff == 0 {
+                break;
+            }
+            let step = diff / 2;
+            let next = if step == 0 { current + diff } else { current + step };
+            if next == value {
+                break;
+            }
+            candidates.push(next);
+            current = next;
+        }
+        candidates
+    }}
+}
+
+// Magnitude is deliberately decoupled from `size` here: elsewhere in this file
+// `size` bounds container lengths (and `char` code points), and tying integer
+// ranges to it
+// (as the old `u32` impl did) means almost the entire value space never gets
+// exercised.
+macro_rules! arbitrary_signed_int {
+    ($ty:ty) => {
+        impl Arbitrary for $ty {
+            fn grow(rng: &mut StdRng, _size: f64) -> $ty {
+                if Range::new(0, PROBLEM_VALUE_CHANCE).ind_sample(rng) == 0 {
+                    let problems: [$ty; 5] =
+                        [0, 1, -1, $ty::MIN, $ty::MAX];
+                    problems[Range::new(0, problems.len()).ind_sample(rng)]
+                } else {
+                    rng.gen::<$ty>()
+                }
+            }
+            fn shrink(&self) -> Box<Iterator<Item=$ty>> {
+                let value = *self;
+                if value == 0 {
+                    Box::new(None.into_iter())
+                } else {
+                    let candidates: Vec<$ty> = int_shrink_candidates!(value);
+                    Box::new(candidates.into_iter())
+                }
+            }
+        }
+    }
+}
+
+macro_rules! arbitrary_unsigned_int {
+    ($ty:ty) => {
+        impl Arbitrary for $ty {
+            fn grow(rng: &mut StdRng, _size: f64) -> $ty {
+                if Range::new(0, PROBLEM_VALUE_CHANCE).ind_sample(rng) == 0 {
+                    let problems: [$ty; 3] = [0, 1, $ty::MAX];
+                    problems[Range::new(0, problems.len()).ind_sample(rng)]
+                } else {
+                    rng.gen::<$ty>()
+                }
+            }
+            fn shrink(&self) -> Box<Iterator<Item=$ty>> {
+                let value = *self;
+                if value == 0 {
+                    Box::new(None.into_iter())
+                } else {
+                    let candidates: Vec<$ty> = int_shrink_candidates!(value);
+                    Box::new(candidates.into_iter())
+                }
+            }
+        }
+    }
+}
+
+arbitrary_signed_int!(i8);
+arbitrary_signed_int!(i16);
+arbitrary_signed_int!(i32);
+arbitrary_signed_int!(i64);
+arbitrary_signed_int!(i128);
+arbitrary_signed_int!(isize);
+
+arbitrary_unsigned_int!(u8);
+arbitrary_unsigned_int!(u16);
+arbitrary_unsigned_int!(u32);
+arbitrary_unsigned_int!(u64);
+arbitrary_unsigned_int!(u128);
+arbitrary_unsigned_int!(usize);
+
+// Encodes as a fixed-width native-endian (in-memory) byte pattern via
+// `mem::transmute`, the same trick the float impls below use to get at raw
+// bits. `decode` zero-pads on a truncated buffer rather than panicking,
+// since `load_failures` already treats a short tail as the end of the file.
+macro_rules! replayable_int {
+    ($ty:ty, $size:expr) => {
+        impl Replayable for $ty {
+            fn encode(&self, out: &mut Vec<u8>) {
+                let bytes: [u8; $size] = unsafe { mem::transmute(*self) };
+                out.extend(bytes.iter().cloned());
+            }
+            fn decode(bytes: &mut &[u8]) -> $ty {
+                let mut buf = [0u8; $size];
+                let n = min($size, bytes.len());
+                buf[..n].clone_from_slice(&bytes[..n]);
+                *bytes = &bytes[n..];
+                unsafe { mem::transmute(buf) }
+            }
+        }
+    }
+}
+
+replayable_int!(i8, 1);
+replayable_int!(i16, 2);
+replayable_int!(i32, 4);
+replayable_int!(i64, 8);
+replayable_int!(i128, 16);
+replayable_int!(isize, 8);
+
+replayable_int!(u8, 1);
+replayable_int!(u16, 2);
+replayable_int!(u32, 4);
+replayable_int!(u64, 8);
+replayable_int!(u128, 16);
+replayable_int!(usize, 8);
+
+// Floats shrink toward both zero and their integer part (truncated toward
+// zero), since "becomes an integer" is often as useful a minimization as
+// "becomes small" for tripping up code that assumes fractional input. NaN and
+// the infinities (both generated by the problem table in `grow`) need their
+// own candidates: halving
+// is a no-op on an infinity and NaN isn't even equal to itself, so neither
+// can rely on the finite-value logic to produce something strictly smaller.
+macro_rules! arbitrary_float {
+    ($ty:ty, $bits:ty) => {
+        impl Arbitrary for $ty {
+            fn grow(rng: &mut StdRng, _size: f64) -> $ty {
+                if Range::new(0, PROBLEM_VALUE_CHANCE).ind_sample(rng) == 0 {
+                    let problems: [$ty; 10] = [
+                        0.0, -0.0, 1.0, -1.0,
+                        $ty::MIN, $ty::MAX, $ty::EPSILON,
+                        $ty::INFINITY, $ty::NEG_INFINITY, $ty::NAN,
+                    ];
+                    problems[Range::new(0, problems.len()).ind_sample(rng)]
+                } else {
+                    loop {
+                        let bits: $bits = rng.gen();
+                        let value: $ty = unsafe { mem::transmute(bits) };
+                        if !value.is_nan() {
+                            return value;
+                        }
+                    }
+                }
+            }
+            fn shrink(&self) -> Box<Iterator<Item=$ty>> {
+                let value = *self;
+                if value.is_nan() {
+                    // NaN has no smaller NaN to offer; shrink straight to the
+                    // same base case every other float shrinks toward.
+                    return Box::new(Some(0.0).into_iter());
+                }
+                if value == 0.0 {
+                    return Box::new(None.into_iter());
+                }
+                let mut candidates = vec![0.0];
+                if value.is_infinite() {
+                    // value / 2.0 is a no-op on an infinity, so step toward
+                    // the largest finite value of the same sign instead.
+                    candidates.push(if value > 0.0 { $ty::MAX } else { $ty::MIN });
+                } else {
+                    let halved = value / 2.0;
+                    if halved != value {
+                        candidates.push(halved);
+                    }
+                    let towards_integer = value.trunc();
+                    if towards_integer != value {
+                        candidates.push(towards_integer);
+                    }
+                }
+                Box::new(candidates.into_iter())
+            }
+        }
     }
-    fn shrink(rng: &mut StdRng, value: &u32) -> u32 {
-        Range::new(0, *value + 1).ind_sample(rng)
+}
+
+arbitrary_float!(f32, u32);
+arbitrary_float!(f64, u64);
+
+// Delegates to the same-width unsigned int's `Replayable`, round-tripping
+// through its bit pattern rather than writing the float directly (which
+// would need its own NaN/-0.0-safe handling).
+macro_rules! replayable_float {
+    ($ty:ty, $bits:ty) => {
+        impl Replayable for $ty {
+            fn encode(&self, out: &mut Vec<u8>) {
+                let bits: $bits = unsafe { mem::transmute(*self) };
+                bits.encode(out);
+            }
+            fn decode(bytes: &mut &[u8]) -> $ty {
+                let bits: $bits = Replayable::decode(bytes);
+                unsafe { mem::transmute(bits) }
+            }
+        }
     }
 }
 
+replayable_float!(f32, u32);
+replayable_float!(f64, u64);
+
 impl Arbitrary for char {
     fn grow(rng: &mut StdRng, size: f64) -> char {
         let char_size = min(size.to_u32().unwrap(), char::MAX as u32);
         let char_code = Range::new(0, char_size + 1).ind_sample(rng);
         char::from_u32(char_code).unwrap() // cant fail because we used char::MAX
     }
-    fn shrink(rng: &mut StdRng, value: &char) -> char {
-        let char_code = Range::new(0, *value as u32 + 1).ind_sample(rng);
-        char::from_u32(char_code).unwrap() // cant fail because value <= char::MAX
+    fn shrink(&self) -> Box<Iterator<Item=char>> {
+        let code = *self as u32;
+        let candidates: Vec<char> = int_shrink_candidates!(code).into_iter()
+            .filter_map(char::from_u32)
+            .collect();
+        Box::new(candidates.into_iter())
+    }
+}
+
+impl Replayable for char {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (*self as u32).encode(out);
+    }
+    fn decode(bytes: &mut &[u8]) -> char {
+        let code: u32 = Replayable::decode(bytes);
+        char::from_u32(code).unwrap_or('\u{0}')
     }
 }
 
@@ -123,17 +523,42 @@ impl Arbitrary for String {
         }
         string
     }
-    fn shrink(rng: &mut StdRng, value: &String) -> String {
-        let mut chars = value.chars().collect::<Vec<char>>();
-        if chars.len() > 0 {
-            let ix = Range::new(0, chars.len()).ind_sample(rng);
-            let char = chars.remove(ix);
-            if random() {
-                chars.insert(ix, Arbitrary::shrink(rng, &char))
+    fn shrink(&self) -> Box<Iterator<Item=String>> {
+        let chars = self.chars().collect::<Vec<char>>();
+        let mut candidates: Vec<String> = Vec::new();
+
+        let mut chunk_size = chars.len();
+        while chunk_size > 0 {
+            let mut i = 0;
+            while i + chunk_size <= chars.len() {
+                let mut without_chunk = chars.clone();
+                without_chunk.drain(i..i + chunk_size);
+                candidates.push(without_chunk.into_iter().collect());
+                i += chunk_size;
             }
+            chunk_size /= 2;
         }
-        let value = chars.drain().collect();
-        value
+
+        for i in 0..chars.len() {
+            for shrunk_char in chars[i].shrink() {
+                let mut with_shrunk_char = chars.clone();
+                with_shrunk_char[i] = shrunk_char;
+                candidates.push(with_shrunk_char.into_iter().collect());
+            }
+        }
+
+        Box::new(candidates.into_iter())
+    }
+}
+
+impl Replayable for String {
+    fn encode(&self, out: &mut Vec<u8>) {
+        let chars: Vec<char> = self.chars().collect();
+        chars.encode(out);
+    }
+    fn decode(bytes: &mut &[u8]) -> String {
+        let chars: Vec<char> = Replayable::decode(bytes);
+        chars.into_iter().collect()
     }
 }
 
@@ -144,13 +569,26 @@ impl<A: Arbitrary + Clone, B: Arbitrary + Clone> Arbitrary for (A,B) {
     fn grow(rng: &mut StdRng, size: f64) -> (A,B) {
         (Arbitrary::grow(rng, size), Arbitrary::grow(rng, size))
     }
-    fn shrink(rng: &mut StdRng, value: &(A,B)) -> (A,B) {
-        let (a, b) = value.clone();
-        match Range::new(0, 2).ind_sample(rng) {
-            0 => (Arbitrary::shrink(rng, &a), b),
-            1 => (a, Arbitrary::shrink(rng, &b)),
-            _ => unreachable!(),
+    fn shrink(&self) -> Box<Iterator<Item=(A,B)>> {
+        let (ref a, ref b) = *self;
+        let mut candidates: Vec<(A,B)> = Vec::new();
+        for shrunk_a in a.shrink() {
+            candidates.push((shrunk_a, b.clone()));
         }
+        for shrunk_b in b.shrink() {
+            candidates.push((a.clone(), shrunk_b));
+        }
+        Box::new(candidates.into_iter())
+    }
+}
+
+impl<A: Replayable, B: Replayable> Replayable for (A,B) {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.0.encode(out);
+        self.1.encode(out);
+    }
+    fn decode(bytes: &mut &[u8]) -> (A,B) {
+        (Replayable::decode(bytes), Replayable::decode(bytes))
     }
 }
 
@@ -158,14 +596,30 @@ impl<A: Arbitrary + Clone, B: Arbitrary + Clone, C: Arbitrary + Clone> Arbitrary
     fn grow(rng: &mut StdRng, size: f64) -> (A,B,C) {
         (Arbitrary::grow(rng, size), Arbitrary::grow(rng, size), Arbitrary::grow(rng, size))
     }
-    fn shrink(rng: &mut StdRng, value: &(A,B,C)) -> (A,B,C) {
-        let (a, b, c) = value.clone();
-        match Range::new(0, 3).ind_sample(rng) {
-            0 => (Arbitrary::shrink(rng, &a), b, c),
-            1 => (a, Arbitrary::shrink(rng, &b), c),
-            2 => (a, b, Arbitrary::shrink(rng, &c)),
-            _ => unreachable!(),
+    fn shrink(&self) -> Box<Iterator<Item=(A,B,C)>> {
+        let (ref a, ref b, ref c) = *self;
+        let mut candidates: Vec<(A,B,C)> = Vec::new();
+        for shrunk_a in a.shrink() {
+            candidates.push((shrunk_a, b.clone(), c.clone()));
+        }
+        for shrunk_b in b.shrink() {
+            candidates.push((a.clone(), shrunk_b, c.clone()));
         }
+        for shrunk_c in c.shrink() {
+            candidates.push((a.clone(), b.clone(), shrunk_c));
+        }
+        Box::new(candidates.into_iter())
+    }
+}
+
+impl<A: Replayable, B: Replayable, C: Replayable> Replayable for (A,B,C) {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.0.encode(out);
+        self.1.encode(out);
+        self.2.encode(out);
+    }
+    fn decode(bytes: &mut &[u8]) -> (A,B,C) {
+        (Replayable::decode(bytes), Replayable::decode(bytes), Replayable::decode(bytes))
     }
 }
 
@@ -173,15 +627,34 @@ impl<A: Arbitrary + Clone, B: Arbitrary + Clone, C: Arbitrary + Clone, D: Arbitr
     fn grow(rng: &mut StdRng, size: f64) -> (A,B,C,D) {
         (Arbitrary::grow(rng, size), Arbitrary::grow(rng, size), Arbitrary::grow(rng, size), Arbitrary::grow(rng, size))
     }
-    fn shrink(rng: &mut StdRng, value: &(A,B,C,D)) -> (A,B,C,D) {
-        let (a, b, c, d) = value.clone();
-        match Range::new(0, 4).ind_sample(rng) {
-            0 => (Arbitrary::shrink(rng, &a), b, c, d),
-            1 => (a, Arbitrary::shrink(rng, &b), c, d),
-            2 => (a, b, Arbitrary::shrink(rng, &c), d),
-            3 => (a, b, c, Arbitrary::shrink(rng, &d)),
-            _ => unreachable!(),
+    fn shrink(&self) -> Box<Iterator<Item=(A,B,C,D)>> {
+        let (ref a, ref b, ref c, ref d) = *self;
+        let mut candidates: Vec<(A,B,C,D)> = Vec::new();
+        for shrunk_a in a.shrink() {
+            candidates.push((shrunk_a, b.clone(), c.clone(), d.clone()));
+        }
+        for shrunk_b in b.shrink() {
+            candidates.push((a.clone(), shrunk_b, c.clone(), d.clone()));
         }
+        for shrunk_c in c.shrink() {
+            candidates.push((a.clone(), b.clone(), shrunk_c, d.clone()));
+        }
+        for shrunk_d in d.shrink() {
+            candidates.push((a.clone(), b.clone(), c.clone(), shrunk_d));
+        }
+        Box::new(candidates.into_iter())
+    }
+}
+
+impl<A: Replayable, B: Replayable, C: Replayable, D: Replayable> Replayable for (A,B,C,D) {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.0.encode(out);
+        self.1.encode(out);
+        self.2.encode(out);
+        self.3.encode(out);
+    }
+    fn decode(bytes: &mut &[u8]) -> (A,B,C,D) {
+        (Replayable::decode(bytes), Replayable::decode(bytes), Replayable::decode(bytes), Replayable::decode(bytes))
     }
 }
 
@@ -189,16 +662,39 @@ impl<A: Arbitrary + Clone, B: Arbitrary + Clone, C: Arbitrary + Clone, D: Arbitr
     fn grow(rng: &mut StdRng, size: f64) -> (A,B,C,D,E) {
         (Arbitrary::grow(rng, size), Arbitrary::grow(rng, size), Arbitrary::grow(rng, size), Arbitrary::grow(rng, size), Arbitrary::grow(rng, size))
     }
-    fn shrink(rng: &mut StdRng, value: &(A,B,C,D,E)) -> (A,B,C,D,E) {
-        let (a, b, c, d, e) = value.clone();
-        match Range::new(0, 5).ind_sample(rng) {
-            0 => (Arbitrary::shrink(rng, &a), b, c, d, e),
-            1 => (a, Arbitrary::shrink(rng, &b), c, d, e),
-            2 => (a, b, Arbitrary::shrink(rng, &c), d, e),
-            3 => (a, b, c, Arbitrary::shrink(rng, &d), e),
-            4 => (a, b, c, d, Arbitrary::shrink(rng, &e)),
-            _ => unreachable!(),
+    fn shrink(&self) -> Box<Iterator<Item=(A,B,C,D,E)>> {
+        let (ref a, ref b, ref c, ref d, ref e) = *self;
+        let mut candidates: Vec<(A,B,C,D,E)> = Vec::new();
+        for shrunk_a in a.shrink() {
+            candidates.push((shrunk_a, b.clone(), c.clone(), d.clone(), e.clone()));
+        }
+        for shrunk_b in b.shrink() {
+            candidates.push((a.clone(), shrunk_b, c.clone(), d.clone(), e.clone()));
+        }
+        for shrunk_c in c.shrink() {
+            candidates.push((a.clone(), b.clone(), shrunk_c, d.clone(), e.clone()));
         }
+        for shrunk_d in d.shrink() {
+            candidates.push((a.clone(), b.clone(), c.clone(), shrunk_d, e.clone()));
+        }
+        for shrunk_e in e.shrink() {
+            candidates.push((a.clone(), b.clone(), c.clone(), d.clone(), shrunk_e));
+        }
+        Box::new(candidates.into_iter())
+    }
+}
+
+impl<A: Replayable, B: Replayable, C: Replayable, D: Replayable, E: Replayable> Replayable for (A,B,C,D,E) {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.0.encode(out);
+        self.1.encode(out);
+        self.2.encode(out);
+        self.3.encode(out);
+        self.4.encode(out);
+    }
+    fn decode(bytes: &mut &[u8]) -> (A,B,C,D,E) {
+        (Replayable::decode(bytes), Replayable::decode(bytes), Replayable::decode(bytes),
+         Replayable::decode(bytes), Replayable::decode(bytes))
     }
 }
 
@@ -213,19 +709,694 @@ impl<A: Arbitrary + Clone> Arbitrary for Vec<A> {
         }
         vec
     }
-    fn shrink(rng: &mut StdRng, value: &Vec<A>) -> Vec<A> {
-        let mut vec = value.clone();
-        if vec.len() > 0 {
-            let ix = Range::new(0, vec.len()).ind_sample(rng);
-            let elem = vec.remove(ix);
-            if random() {
-                vec.insert(ix, Arbitrary::shrink(rng, &elem))
+    fn shrink(&self) -> Box<Iterator<Item=Vec<A>>> {
+        let mut candidates: Vec<Vec<A>> = Vec::new();
+
+        let mut chunk_size = self.len();
+        while chunk_size > 0 {
+            let mut i = 0;
+            while i + chunk_size <= self.len() {
+                let mut without_chunk = self.clone();
+                without_chunk.drain(i..i + chunk_size);
+                candidates.push(without_chunk);
+                i += chunk_size;
+            }
+            chunk_size /= 2;
+        }
+
+        for i in 0..self.len() {
+            for shrunk_elem in self[i].shrink() {
+                let mut with_shrunk_elem = self.clone();
+                with_shrunk_elem[i] = shrunk_elem;
+                candidates.push(with_shrunk_elem);
             }
         }
+
+        Box::new(candidates.into_iter())
+    }
+}
+
+// Length-prefixed (a `u64` length, then that many elements): the same
+// framing `append_failure` uses for a whole entry. `String` (and so `PathBuf`
+// and `OsString`) encodes through this impl; the other collection impls below
+// write the same prefix themselves.
+impl<A: Replayable> Replayable for Vec<A> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (self.len() as u64).encode(out);
+        for item in self.iter() {
+            item.encode(out);
+        }
+    }
+    fn decode(bytes: &mut &[u8]) -> Vec<A> {
+        let len = u64::decode(bytes) as usize;
+        let mut vec = Vec::with_capacity(len);
+        for _ in 0..len {
+            vec.push(Replayable::decode(bytes));
+        }
         vec
     }
 }
 
+impl<A: Arbitrary + Clone> Arbitrary for Option<A> {
+    fn grow(rng: &mut StdRng, size: f64) -> Option<A> {
+        if rng.gen() {
+            Some(Arbitrary::grow(rng, size))
+        } else {
+            None
+        }
+    }
+    fn shrink(&self) -> Box<Iterator<Item=Option<A>>> {
+        match *self {
+            None => Box::new(None.into_iter()),
+            Some(ref value) => {
+                let mut candidates: Vec<Option<A>> = vec![None];
+                for shrunk in value.shrink() {
+                    candidates.push(Some(shrunk));
+                }
+                Box::new(candidates.into_iter())
+            }
+        }
+    }
+}
+
+impl<A: Replayable> Replayable for Option<A> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        match *self {
+            None => out.push(0),
+            Some(ref value) => {
+                out.push(1);
+                value.encode(out);
+            }
+        }
+    }
+    fn decode(bytes: &mut &[u8]) -> Option<A> {
+        let tag = decode_tag(bytes);
+        if tag == 0 { None } else { Some(Replayable::decode(bytes)) }
+    }
+}
+
+impl<A: Arbitrary + Clone, B: Arbitrary + Clone> Arbitrary for Result<A,B> {
+    fn grow(rng: &mut StdRng, size: f64) -> Result<A,B> {
+        if rng.gen() {
+            Ok(Arbitrary::grow(rng, size))
+        } else {
+            Err(Arbitrary::grow(rng, size))
+        }
+    }
+    fn shrink(&self) -> Box<Iterator<Item=Result<A,B>>> {
+        match *self {
+            Ok(ref value) => Box::new(value.shrink().map(|v| Ok(v))),
+            Err(ref value) => Box::new(value.shrink().map(|v| Err(v))),
+        }
+    }
+}
+
+impl<A: Replayable, B: Replayable> Replayable for Result<A,B> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        match *self {
+            Ok(ref value) => { out.push(0); value.encode(out); }
+            Err(ref value) => { out.push(1); value.encode(out); }
+        }
+    }
+    fn decode(bytes: &mut &[u8]) -> Result<A,B> {
+        let tag = decode_tag(bytes);
+        if tag == 0 { Ok(Replayable::decode(bytes)) } else { Err(Replayable::decode(bytes)) }
+    }
+}
+
+impl<K: Arbitrary + Clone + Eq + Hash, V: Arbitrary + Clone> Arbitrary for HashMap<K,V> {
+    fn grow(rng: &mut StdRng, size: f64) -> HashMap<K,V> {
+        let length = Range::new(0, size.to_uint().unwrap() + 1).ind_sample(rng);
+        let mut map = HashMap::with_capacity(length);
+        for _ in (0..length) {
+            let key = Arbitrary::grow(rng, size);
+            let value = Arbitrary::grow(rng, size);
+            map.insert(key, value);
+        }
+        map
+    }
+    fn shrink(&self) -> Box<Iterator<Item=HashMap<K,V>>> {
+        let entries: Vec<(K,V)> = self.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+        let mut candidates: Vec<HashMap<K,V>> = Vec::new();
+        for &(ref key, ref value) in entries.iter() {
+            let mut without_entry = self.clone();
+            without_entry.remove(key);
+            candidates.push(without_entry);
+
+            for shrunk_key in key.shrink() {
+                let mut with_shrunk_key = self.clone();
+                with_shrunk_key.remove(key);
+                with_shrunk_key.insert(shrunk_key, value.clone());
+                candidates.push(with_shrunk_key);
+            }
+
+            for shrunk_value in value.shrink() {
+                let mut with_shrunk_value = self.clone();
+                with_shrunk_value.insert(key.clone(), shrunk_value);
+                candidates.push(with_shrunk_value);
+            }
+        }
+        Box::new(candidates.into_iter())
+    }
+}
+
+impl<K: Replayable + Eq + Hash, V: Replayable> Replayable for HashMap<K,V> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        let entries: Vec<(&K,&V)> = self.iter().collect();
+        (entries.len() as u64).encode(out);
+        for (key, value) in entries {
+            key.encode(out);
+            value.encode(out);
+        }
+    }
+    fn decode(bytes: &mut &[u8]) -> HashMap<K,V> {
+        let len = u64::decode(bytes) as usize;
+        let mut map = HashMap::with_capacity(len);
+        for _ in 0..len {
+            let key = Replayable::decode(bytes);
+            let value = Replayable::decode(bytes);
+            map.insert(key, value);
+        }
+        map
+    }
+}
+
+impl<K: Arbitrary + Clone + Ord, V: Arbitrary + Clone> Arbitrary for BTreeMap<K,V> {
+    fn grow(rng: &mut StdRng, size: f64) -> BTreeMap<K,V> {
+        let length = Range::new(0, size.to_uint().unwrap() + 1).ind_sample(rng);
+        let mut map = BTreeMap::new();
+        for _ in (0..length) {
+            let key = Arbitrary::grow(rng, size);
+            let value = Arbitrary::grow(rng, size);
+            map.insert(key, value);
+        }
+        map
+    }
+    fn shrink(&self) -> Box<Iterator<Item=BTreeMap<K,V>>> {
+        let entries: Vec<(K,V)> = self.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+        let mut candidates: Vec<BTreeMap<K,V>> = Vec::new();
+        for &(ref key, ref value) in entries.iter() {
+            let mut without_entry = self.clone();
+            without_entry.remove(key);
+            candidates.push(without_entry);
+
+            for shrunk_key in key.shrink() {
+                let mut with_shrunk_key = self.clone();
+                with_shrunk_key.remove(key);
+                with_shrunk_key.insert(shrunk_key, value.clone());
+                candidates.push(with_shrunk_key);
+            }
+
+            for shrunk_value in value.shrink() {
+                let mut with_shrunk_value = self.clone();
+                with_shrunk_value.insert(key.clone(), shrunk_value);
+                candidates.push(with_shrunk_value);
+            }
+        }
+        Box::new(candidates.into_iter())
+    }
+}
+
+impl<K: Replayable + Ord, V: Replayable> Replayable for BTreeMap<K,V> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        let entries: Vec<(&K,&V)> = self.iter().collect();
+        (entries.len() as u64).encode(out);
+        for (key, value) in entries {
+            key.encode(out);
+            value.encode(out);
+        }
+    }
+    fn decode(bytes: &mut &[u8]) -> BTreeMap<K,V> {
+        let len = u64::decode(bytes) as usize;
+        let mut map = BTreeMap::new();
+        for _ in 0..len {
+            let key = Replayable::decode(bytes);
+            let value = Replayable::decode(bytes);
+            map.insert(key, value);
+        }
+        map
+    }
+}
+
+impl<A: Arbitrary + Clone + Eq + Hash> Arbitrary for HashSet<A> {
+    fn grow(rng: &mut StdRng, size: f64) -> HashSet<A> {
+        let length = Range::new(0, size.to_uint().unwrap() + 1).ind_sample(rng);
+        let mut set = HashSet::with_capacity(length);
+        for _ in (0..length) {
+            set.insert(Arbitrary::grow(rng, size));
+        }
+        set
+    }
+    fn shrink(&self) -> Box<Iterator<Item=HashSet<A>>> {
+        let elems: Vec<A> = self.iter().cloned().collect();
+        let mut candidates: Vec<HashSet<A>> = Vec::new();
+        for elem in elems.iter() {
+            let mut without_elem = self.clone();
+            without_elem.remove(elem);
+            candidates.push(without_elem);
+
+            for shrunk_elem in elem.shrink() {
+                let mut with_shrunk_elem = self.clone();
+                with_shrunk_elem.remove(elem);
+                with_shrunk_elem.insert(shrunk_elem);
+                candidates.push(with_shrunk_elem);
+            }
+        }
+        Box::new(candidates.into_iter())
+    }
+}
+
+impl<A: Replayable + Eq + Hash> Replayable for HashSet<A> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (self.len() as u64).encode(out);
+        for elem in self.iter() {
+            elem.encode(out);
+        }
+    }
+    fn decode(bytes: &mut &[u8]) -> HashSet<A> {
+        let len = u64::decode(bytes) as usize;
+        let mut set = HashSet::with_capacity(len);
+        for _ in 0..len {
+            set.insert(Replayable::decode(bytes));
+        }
+        set
+    }
+}
+
+impl<A: Arbitrary + Clone + Ord> Arbitrary for BTreeSet<A> {
+    fn grow(rng: &mut StdRng, size: f64) -> BTreeSet<A> {
+        let length = Range::new(0, size.to_uint().unwrap() + 1).ind_sample(rng);
+        let mut set = BTreeSet::new();
+        for _ in (0..length) {
+            set.insert(Arbitrary::grow(rng, size));
+        }
+        set
+    }
+    fn shrink(&self) -> Box<Iterator<Item=BTreeSet<A>>> {
+        let elems: Vec<A> = self.iter().cloned().collect();
+        let mut candidates: Vec<BTreeSet<A>> = Vec::new();
+        for elem in elems.iter() {
+            let mut without_elem = self.clone();
+            without_elem.remove(elem);
+            candidates.push(without_elem);
+
+            for shrunk_elem in elem.shrink() {
+                let mut with_shrunk_elem = self.clone();
+                with_shrunk_elem.remove(elem);
+                with_shrunk_elem.insert(shrunk_elem);
+                candidates.push(with_shrunk_elem);
+            }
+        }
+        Box::new(candidates.into_iter())
+    }
+}
+
+impl<A: Replayable + Ord> Replayable for BTreeSet<A> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (self.len() as u64).encode(out);
+        for elem in self.iter() {
+            elem.encode(out);
+        }
+    }
+    fn decode(bytes: &mut &[u8]) -> BTreeSet<A> {
+        let len = u64::decode(bytes) as usize;
+        let mut set = BTreeSet::new();
+        for _ in 0..len {
+            set.insert(Replayable::decode(bytes));
+        }
+        set
+    }
+}
+
+// Delegates to `Vec<A>`'s shrink (chunk removal + per-element shrinking)
+// rather than duplicating it, since both are just sequences of `A`.
+impl<A: Arbitrary + Clone> Arbitrary for VecDeque<A> {
+    fn grow(rng: &mut StdRng, size: f64) -> VecDeque<A> {
+        let length = Range::new(0, size.to_uint().unwrap() + 1).ind_sample(rng);
+        let mut deque = VecDeque::with_capacity(length);
+        for _ in (0..length) {
+            deque.push_back(Arbitrary::grow(rng, size));
+        }
+        deque
+    }
+    fn shrink(&self) -> Box<Iterator<Item=VecDeque<A>>> {
+        let vec: Vec<A> = self.iter().cloned().collect();
+        Box::new(vec.shrink().map(|v| v.into_iter().collect()))
+    }
+}
+
+// Writes the same length-prefixed framing as `Vec<A>`'s encoding.
+impl<A: Replayable> Replayable for VecDeque<A> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (self.len() as u64).encode(out);
+        for item in self.iter() {
+            item.encode(out);
+        }
+    }
+    fn decode(bytes: &mut &[u8]) -> VecDeque<A> {
+        let len = u64::decode(bytes) as usize;
+        let mut deque = VecDeque::with_capacity(len);
+        for _ in 0..len {
+            deque.push_back(Replayable::decode(bytes));
+        }
+        deque
+    }
+}
+
+impl<A: Arbitrary + Clone> Arbitrary for LinkedList<A> {
+    fn grow(rng: &mut StdRng, size: f64) -> LinkedList<A> {
+        let length = Range::new(0, size.to_uint().unwrap() + 1).ind_sample(rng);
+        let mut list = LinkedList::new();
+        for _ in (0..length) {
+            list.push_back(Arbitrary::grow(rng, size));
+        }
+        list
+    }
+    fn shrink(&self) -> Box<Iterator<Item=LinkedList<A>>> {
+        let vec: Vec<A> = self.iter().cloned().collect();
+        Box::new(vec.shrink().map(|v| v.into_iter().collect()))
+    }
+}
+
+impl<A: Replayable> Replayable for LinkedList<A> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (self.len() as u64).encode(out);
+        for item in self.iter() {
+            item.encode(out);
+        }
+    }
+    fn decode(bytes: &mut &[u8]) -> LinkedList<A> {
+        let len = u64::decode(bytes) as usize;
+        let mut list = LinkedList::new();
+        for _ in 0..len {
+            list.push_back(Replayable::decode(bytes));
+        }
+        list
+    }
+}
+
+impl<A: Arbitrary + Clone> Arbitrary for Box<A> {
+    fn grow(rng: &mut StdRng, size: f64) -> Box<A> {
+        Box::new(Arbitrary::grow(rng, size))
+    }
+    fn shrink(&self) -> Box<Iterator<Item=Box<A>>> {
+        Box::new((**self).shrink().map(Box::new))
+    }
+}
+
+impl<A: Replayable> Replayable for Box<A> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (**self).encode(out);
+    }
+    fn decode(bytes: &mut &[u8]) -> Box<A> {
+        Box::new(Replayable::decode(bytes))
+    }
+}
+
+impl<A: Arbitrary + Clone> Arbitrary for Rc<A> {
+    fn grow(rng: &mut StdRng, size: f64) -> Rc<A> {
+        Rc::new(Arbitrary::grow(rng, size))
+    }
+    fn shrink(&self) -> Box<Iterator<Item=Rc<A>>> {
+        Box::new((**self).shrink().map(Rc::new))
+    }
+}
+
+impl<A: Replayable> Replayable for Rc<A> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (**self).encode(out);
+    }
+    fn decode(bytes: &mut &[u8]) -> Rc<A> {
+        Rc::new(Replayable::decode(bytes))
+    }
+}
+
+impl<A: Arbitrary + Clone> Arbitrary for Arc<A> {
+    fn grow(rng: &mut StdRng, size: f64) -> Arc<A> {
+        Arc::new(Arbitrary::grow(rng, size))
+    }
+    fn shrink(&self) -> Box<Iterator<Item=Arc<A>>> {
+        Box::new((**self).shrink().map(Arc::new))
+    }
+}
+
+impl<A: Replayable> Replayable for Arc<A> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (**self).encode(out);
+    }
+    fn decode(bytes: &mut &[u8]) -> Arc<A> {
+        Arc::new(Replayable::decode(bytes))
+    }
+}
+
+impl Arbitrary for Duration {
+    fn grow(rng: &mut StdRng, size: f64) -> Duration {
+        let secs: u64 = Arbitrary::grow(rng, size);
+        let nanos = Range::new(0, 1_000_000_000).ind_sample(rng);
+        Duration::new(secs, nanos)
+    }
+    fn shrink(&self) -> Box<Iterator<Item=Duration>> {
+        let secs = self.as_secs();
+        let nanos = self.subsec_nanos();
+        let mut candidates: Vec<Duration> = Vec::new();
+        for shrunk_secs in secs.shrink() {
+            candidates.push(Duration::new(shrunk_secs, nanos));
+        }
+        for shrunk_nanos in int_shrink_candidates!(nanos) {
+            candidates.push(Duration::new(secs, shrunk_nanos));
+        }
+        Box::new(candidates.into_iter())
+    }
+}
+
+impl Replayable for Duration {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.as_secs().encode(out);
+        self.subsec_nanos().encode(out);
+    }
+    fn decode(bytes: &mut &[u8]) -> Duration {
+        let secs = u64::decode(bytes);
+        let nanos = u32::decode(bytes);
+        Duration::new(secs, nanos)
+    }
+}
+
+// Full-range `u64` seconds (as `Duration`'s `Arbitrary` impl draws them) vastly
+// exceeds what `UNIX_EPOCH + offset` can represent on any OS, so `Add`/`Sub`
+// would panic on overflow almost every call. Cap the offset to a range well
+// within any platform's `SystemTime` representation instead.
+const MAX_SYSTEM_TIME_OFFSET_SECS: u64 = 1_000_000_000; // ~31 years
+
+impl Arbitrary for SystemTime {
+    fn grow(rng: &mut StdRng, _size: f64) -> SystemTime {
+        let secs = Range::new(0, MAX_SYSTEM_TIME_OFFSET_SECS + 1).ind_sample(rng);
+        let nanos = Range::new(0, 1_000_000_000).ind_sample(rng);
+        let offset = Duration::new(secs, nanos);
+        if rng.gen() {
+            UNIX_EPOCH + offset
+        } else {
+            UNIX_EPOCH - offset
+        }
+    }
+    fn shrink(&self) -> Box<Iterator<Item=SystemTime>> {
+        let (offset, before_epoch) = match self.duration_since(UNIX_EPOCH) {
+            Ok(offset) => (offset, false),
+            Err(err) => (err.duration(), true),
+        };
+        let candidates: Vec<SystemTime> = offset.shrink()
+            .map(|shrunk| if before_epoch { UNIX_EPOCH - shrunk } else { UNIX_EPOCH + shrunk })
+            .collect();
+        Box::new(candidates.into_iter())
+    }
+}
+
+impl Replayable for SystemTime {
+    fn encode(&self, out: &mut Vec<u8>) {
+        let (offset, before_epoch) = match self.duration_since(UNIX_EPOCH) {
+            Ok(offset) => (offset, false),
+            Err(err) => (err.duration(), true),
+        };
+        out.push(if before_epoch { 1 } else { 0 });
+        offset.encode(out);
+    }
+    fn decode(bytes: &mut &[u8]) -> SystemTime {
+        let before_epoch = decode_tag(bytes) != 0;
+        let offset: Duration = Replayable::decode(bytes);
+        if before_epoch { UNIX_EPOCH - offset } else { UNIX_EPOCH + offset }
+    }
+}
+
+impl Arbitrary for Ipv4Addr {
+    fn grow(rng: &mut StdRng, size: f64) -> Ipv4Addr {
+        let octets: (u8,u8,u8,u8) = Arbitrary::grow(rng, size);
+        Ipv4Addr::new(octets.0, octets.1, octets.2, octets.3)
+    }
+    fn shrink(&self) -> Box<Iterator<Item=Ipv4Addr>> {
+        let octets = self.octets();
+        let tuple = (octets[0], octets[1], octets[2], octets[3]);
+        Box::new(tuple.shrink().map(|(a, b, c, d)| Ipv4Addr::new(a, b, c, d)))
+    }
+}
+
+impl Replayable for Ipv4Addr {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend(self.octets().iter().cloned());
+    }
+    fn decode(bytes: &mut &[u8]) -> Ipv4Addr {
+        // Decode each octet through u8's Replayable impl rather than
+        // indexing bytes[0..4] directly, so a truncated buffer zero-pads
+        // instead of panicking (the same contract decode_tag restores for
+        // IpAddr's own variant tag further below).
+        let octets: (u8, u8, u8, u8) = Replayable::decode(bytes);
+        Ipv4Addr::new(octets.0, octets.1, octets.2, octets.3)
+    }
+}
+
+impl Arbitrary for Ipv6Addr {
+    fn grow(rng: &mut StdRng, size: f64) -> Ipv6Addr {
+        let mut segments = [0u16; 8];
+        for segment in segments.iter_mut() {
+            *segment = Arbitrary::grow(rng, size);
+        }
+        Ipv6Addr::new(segments[0], segments[1], segments[2], segments[3],
+                       segments[4], segments[5], segments[6], segments[7])
+    }
+    fn shrink(&self) -> Box<Iterator<Item=Ipv6Addr>> {
+        let segments = self.segments();
+        let mut candidates: Vec<Ipv6Addr> = Vec::new();
+        for i in 0..8 {
+            for shrunk in segments[i].shrink() {
+                let mut next = segments;
+                next[i] = shrunk;
+                candidates.push(Ipv6Addr::new(next[0], next[1], next[2], next[3],
+                                               next[4], next[5], next[6], next[7]));
+            }
+        }
+        Box::new(candidates.into_iter())
+    }
+}
+
+impl Replayable for Ipv6Addr {
+    fn encode(&self, out: &mut Vec<u8>) {
+        for segment in self.segments().iter() {
+            segment.encode(out);
+        }
+    }
+    fn decode(bytes: &mut &[u8]) -> Ipv6Addr {
+        let mut segments = [0u16; 8];
+        for segment in segments.iter_mut() {
+            *segment = Replayable::decode(bytes);
+        }
+        Ipv6Addr::new(segments[0], segments[1], segments[2], segments[3],
+                       segments[4], segments[5], segments[6], segments[7])
+    }
+}
+
+impl Arbitrary for IpAddr {
+    fn grow(rng: &mut StdRng, size: f64) -> IpAddr {
+        if rng.gen() {
+            IpAddr::V4(Arbitrary::grow(rng, size))
+        } else {
+            IpAddr::V6(Arbitrary::grow(rng, size))
+        }
+    }
+    fn shrink(&self) -> Box<Iterator<Item=IpAddr>> {
+        match *self {
+            IpAddr::V4(ref addr) => Box::new(addr.shrink().map(IpAddr::V4)),
+            IpAddr::V6(ref addr) => Box::new(addr.shrink().map(IpAddr::V6)),
+        }
+    }
+}
+
+impl Replayable for IpAddr {
+    fn encode(&self, out: &mut Vec<u8>) {
+        match *self {
+            IpAddr::V4(ref addr) => { out.push(0); addr.encode(out); }
+            IpAddr::V6(ref addr) => { out.push(1); addr.encode(out); }
+        }
+    }
+    fn decode(bytes: &mut &[u8]) -> IpAddr {
+        let tag = decode_tag(bytes);
+        if tag == 0 { IpAddr::V4(Replayable::decode(bytes)) } else { IpAddr::V6(Replayable::decode(bytes)) }
+    }
+}
+
+impl Arbitrary for SocketAddr {
+    fn grow(rng: &mut StdRng, size: f64) -> SocketAddr {
+        let ip: IpAddr = Arbitrary::grow(rng, size);
+        let port: u16 = Arbitrary::grow(rng, size);
+        SocketAddr::new(ip, port)
+    }
+    fn shrink(&self) -> Box<Iterator<Item=SocketAddr>> {
+        let ip = self.ip();
+        let port = self.port();
+        let mut candidates: Vec<SocketAddr> = Vec::new();
+        for shrunk_ip in ip.shrink() {
+            candidates.push(SocketAddr::new(shrunk_ip, port));
+        }
+        for shrunk_port in port.shrink() {
+            candidates.push(SocketAddr::new(ip, shrunk_port));
+        }
+        Box::new(candidates.into_iter())
+    }
+}
+
+impl Replayable for SocketAddr {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.ip().encode(out);
+        self.port().encode(out);
+    }
+    fn decode(bytes: &mut &[u8]) -> SocketAddr {
+        let ip = Replayable::decode(bytes);
+        let port = Replayable::decode(bytes);
+        SocketAddr::new(ip, port)
+    }
+}
+
+impl Arbitrary for PathBuf {
+    fn grow(rng: &mut StdRng, size: f64) -> PathBuf {
+        let string: String = Arbitrary::grow(rng, size);
+        PathBuf::from(string)
+    }
+    fn shrink(&self) -> Box<Iterator<Item=PathBuf>> {
+        let string = self.to_string_lossy().into_owned();
+        Box::new(string.shrink().map(PathBuf::from))
+    }
+}
+
+impl Replayable for PathBuf {
+    fn encode(&self, out: &mut Vec<u8>) {
+        let string = self.to_string_lossy().into_owned();
+        string.encode(out);
+    }
+    fn decode(bytes: &mut &[u8]) -> PathBuf {
+        let string: String = Replayable::decode(bytes);
+        PathBuf::from(string)
+    }
+}
+
+impl Arbitrary for OsString {
+    fn grow(rng: &mut StdRng, size: f64) -> OsString {
+        let string: String = Arbitrary::grow(rng, size);
+        OsString::from(string)
+    }
+    fn shrink(&self) -> Box<Iterator<Item=OsString>> {
+        let string = self.to_string_lossy().into_owned();
+        Box::new(string.shrink().map(OsString::from))
+    }
+}
+
+impl Replayable for OsString {
+    fn encode(&self, out: &mut Vec<u8>) {
+        let string = self.to_string_lossy().into_owned();
+        string.encode(out);
+    }
+    fn decode(bytes: &mut &[u8]) -> OsString {
+        let string: String = Replayable::decode(bytes);
+        OsString::from(string)
+    }
+}
+
 #[test]
 fn test_panic() {
     fn oh_noes(_: i64) {
@@ -241,6 +1412,8 @@ fn test_shrinking() {
         max_tests: 1000,
         max_shrinks: 2000,
         max_size: 1000.0,
+        name: "test_shrinking".to_string(),
+        replay_dir: None,
     };
     fn test(string: String) {
         assert!(!string.starts_with("o"));
@@ -249,4 +1422,35 @@ fn test_shrinking() {
         Run::Failure{shrunk_input, ..} => assert_eq!(shrunk_input, "o"),
         _ => assert!(false),
     }
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_replay() {
+    let dir = std::env::temp_dir().join("bigcheck_test_replay");
+    let _ = fs::remove_dir_all(&dir);
+    let config: Config = Config {
+        seed: vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10],
+        max_tests: 100,
+        max_shrinks: 200,
+        max_size: 100.0,
+        name: "test_replay".to_string(),
+        replay_dir: Some(dir.clone()),
+    };
+    fn fails_on_empty(v: Vec<i32>) {
+        assert!(!v.is_empty());
+    }
+    match run(fails_on_empty, &config) {
+        Run::Failure{shrunk_input, ..} => assert_eq!(shrunk_input, Vec::new()),
+        _ => assert!(false),
+    }
+    // The failure is now on disk, so a fresh run should replay it before
+    // generating anything: no tests consumed and the same minimal input.
+    match run(fails_on_empty, &config) {
+        Run::Failure{num_tests, shrunk_input, ..} => {
+            assert_eq!(num_tests, 0);
+            assert_eq!(shrunk_input, Vec::new());
+        }
+        _ => assert!(false),
+    }
+    let _ = fs::remove_dir_all(&dir);
+}